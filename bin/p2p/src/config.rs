@@ -14,6 +14,32 @@ pub struct Config {
     /// Topic to subscribe to
     #[serde(default = "default_topic")]
     pub topic: String,
+
+    /// Relay peers to request a `/p2p-circuit` reservation from when we are
+    /// behind NAT
+    #[serde(default = "default_relay_peers")]
+    pub relay_peers: Vec<Multiaddr>,
+
+    /// Run a public Circuit Relay v2 server for other peers
+    #[serde(default = "default_enable_relay_server")]
+    pub enable_relay_server: bool,
+
+    /// Network-load profile (1-5) trading bandwidth for propagation speed
+    #[serde(default = "default_network_load")]
+    pub network_load: u8,
+
+    /// Maximum total simultaneous connections
+    #[serde(default = "default_max_connections")]
+    pub max_connections: usize,
+
+    /// Maximum simultaneous connections per peer, checked when admitting an
+    /// inbound connection (outbound dials are not capped per peer)
+    #[serde(default = "default_max_connections_per_peer")]
+    pub max_connections_per_peer: usize,
+
+    /// Connection slots reserved for outbound-only dials
+    #[serde(default = "default_outbound_connection_slack")]
+    pub outbound_connection_slack: usize,
 }
 
 impl Default for Config {
@@ -21,10 +47,19 @@ impl Default for Config {
         Self {
             bootstrap_peers: default_bootstrap_peers(),
             topic: default_topic(),
+            relay_peers: default_relay_peers(),
+            enable_relay_server: default_enable_relay_server(),
+            network_load: default_network_load(),
+            max_connections: default_max_connections(),
+            max_connections_per_peer: default_max_connections_per_peer(),
+            outbound_connection_slack: default_outbound_connection_slack(),
         }
     }
 }
 
+/// Valid range for the network-load profile.
+pub const NETWORK_LOAD_RANGE: std::ops::RangeInclusive<u8> = 1..=5;
+
 impl Config {
     /// Load configuration from a YAML file, merging with defaults
     pub fn from_file(path: &Path) -> Result<Self> {
@@ -38,7 +73,18 @@ impl Config {
         let config: ConfigYaml =
             serde_yaml::from_str(&content).context("Failed to parse YAML config")?;
 
-        Ok(config.into())
+        let config: Config = config.into();
+
+        if !NETWORK_LOAD_RANGE.contains(&config.network_load) {
+            anyhow::bail!(
+                "network_load must be between {} and {}, got {}",
+                NETWORK_LOAD_RANGE.start(),
+                NETWORK_LOAD_RANGE.end(),
+                config.network_load
+            );
+        }
+
+        Ok(config)
     }
 }
 
@@ -50,6 +96,24 @@ struct ConfigYaml {
 
     #[serde(default)]
     topic: Option<String>,
+
+    #[serde(default)]
+    relay_peers: Option<Vec<String>>,
+
+    #[serde(default)]
+    enable_relay_server: Option<bool>,
+
+    #[serde(default)]
+    network_load: Option<u8>,
+
+    #[serde(default)]
+    max_connections: Option<usize>,
+
+    #[serde(default)]
+    max_connections_per_peer: Option<usize>,
+
+    #[serde(default)]
+    outbound_connection_slack: Option<usize>,
 }
 
 impl From<ConfigYaml> for Config {
@@ -72,9 +136,44 @@ impl From<ConfigYaml> for Config {
 
         let topic = yaml.topic.unwrap_or_else(default_topic);
 
+        let relay_peers = yaml
+            .relay_peers
+            .map(|peers| {
+                peers
+                    .into_iter()
+                    .filter_map(|p| {
+                        p.parse::<Multiaddr>()
+                            .map_err(|e| {
+                                eprintln!("Warning: Failed to parse relay peer '{}': {}", p, e);
+                            })
+                            .ok()
+                    })
+                    .collect()
+            })
+            .unwrap_or_else(default_relay_peers);
+
+        let enable_relay_server = yaml
+            .enable_relay_server
+            .unwrap_or_else(default_enable_relay_server);
+
+        let network_load = yaml.network_load.unwrap_or_else(default_network_load);
+        let max_connections = yaml.max_connections.unwrap_or_else(default_max_connections);
+        let max_connections_per_peer = yaml
+            .max_connections_per_peer
+            .unwrap_or_else(default_max_connections_per_peer);
+        let outbound_connection_slack = yaml
+            .outbound_connection_slack
+            .unwrap_or_else(default_outbound_connection_slack);
+
         Config {
             bootstrap_peers,
             topic,
+            relay_peers,
+            enable_relay_server,
+            network_load,
+            max_connections,
+            max_connections_per_peer,
+            outbound_connection_slack,
         }
     }
 }
@@ -107,3 +206,69 @@ fn default_bootstrap_peers() -> Vec<Multiaddr> {
 fn default_topic() -> String {
     "example-topic".to_string()
 }
+
+/// Default relay peers (none until an operator configures one)
+fn default_relay_peers() -> Vec<Multiaddr> {
+    Vec::new()
+}
+
+/// By default a node does not act as a public relay
+fn default_enable_relay_server() -> bool {
+    false
+}
+
+/// Default network-load profile (balanced)
+fn default_network_load() -> u8 {
+    3
+}
+
+/// Default maximum total simultaneous connections
+fn default_max_connections() -> usize {
+    256
+}
+
+/// Default maximum simultaneous connections per peer
+fn default_max_connections_per_peer() -> usize {
+    1
+}
+
+/// Default connection slots reserved for outbound-only dials
+fn default_outbound_connection_slack() -> usize {
+    16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// Write `contents` to a temp file keyed on `label` and return its path.
+    fn temp_config(label: &str, contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir()
+            .join(format!("p2p-config-{}-{}.yml", std::process::id(), label));
+        let mut file = fs::File::create(&path).unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        path
+    }
+
+    #[test]
+    fn accepts_network_load_in_range() {
+        let path = temp_config("in-range", "network_load: 5\n");
+        let cfg = Config::from_file(&path).unwrap();
+        assert_eq!(cfg.network_load, 5);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn rejects_network_load_out_of_range() {
+        let path = temp_config("out-of-range", "network_load: 9\n");
+        assert!(Config::from_file(&path).is_err());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn missing_file_uses_defaults() {
+        let cfg = Config::from_file(Path::new("/nonexistent/p2p-config.yml")).unwrap();
+        assert_eq!(cfg.network_load, default_network_load());
+    }
+}
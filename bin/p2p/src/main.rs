@@ -1,10 +1,22 @@
 mod config;
+mod metrics;
+mod network;
+mod peer_manager;
 
-use ::futures::StreamExt;
 use anyhow::{Context, Result};
 use clap::Parser;
-use libp2p::{PeerId, Swarm, SwarmBuilder, identity, kad};
+use libp2p::swarm::behaviour::toggle::Toggle;
+use libp2p::{
+    PeerId, Swarm, SwarmBuilder, autonat, dcutr, gossipsub, identify, identity, kad, relay,
+};
+use libp2p_metrics::Metrics;
+use network::{Behaviour, EventLoop};
+use peer_manager::PeerManager;
+use prometheus_client::registry::Registry;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::io::{AsyncBufReadExt, BufReader};
 use tracing::Level;
 use tracing_subscriber::FmtSubscriber;
 
@@ -31,25 +43,73 @@ struct Cli {
     /// Listening port
     #[arg(short, long, default_value = "30333")]
     port: u16,
+
+    /// Serve Prometheus metrics on this port (disabled if unset)
+    #[arg(long)]
+    metrics_port: Option<u16>,
+
+    /// Key type to generate on first run (ignored if an identity already exists)
+    #[arg(long, value_enum, default_value_t = KeyType::Ed25519)]
+    key_type: KeyType,
+}
+
+/// Identity key types the node can generate.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum KeyType {
+    Ed25519,
+    Secp256k1,
 }
 
-/// Load or create a keypair from the identity file
-fn load_or_create_identity(identity_path: &Path) -> Result<identity::Keypair> {
+// Type tags for the self-describing identity file format. A tagged file is a
+// hex-encoded `[tag, ..protobuf_keypair]`; an untagged file is treated as a
+// legacy raw-hex Ed25519 key for backward compatibility.
+const TAG_ED25519: u8 = 1;
+const TAG_SECP256K1: u8 = 2;
+const TAG_RSA: u8 = 3;
+
+/// Load or create a keypair from the identity file.
+///
+/// New files use a self-describing format: a hex-encoded type tag followed by
+/// the protobuf-encoded keypair. Files without a recognised tag are treated as
+/// legacy raw-hex Ed25519 keys. The `key_type` only takes effect when a fresh
+/// identity is created.
+fn load_or_create_identity(
+    identity_path: &Path,
+    key_type: KeyType,
+) -> Result<identity::Keypair> {
     if identity_path.exists() {
         let key_bytes = std::fs::read(identity_path).context("Failed to read identity file")?;
         let key_data = String::from_utf8(key_bytes).context("Identity file is not valid UTF-8")?;
         let decoded = hex::decode(&key_data).context("Failed to decode identity from hex")?;
-        identity::Keypair::ed25519_from_bytes(decoded)
-            .context("Failed to parse identity keypair from bytes")
+
+        match decoded.split_first() {
+            Some((&(TAG_ED25519 | TAG_SECP256K1 | TAG_RSA), protobuf)) => {
+                identity::Keypair::from_protobuf_encoding(protobuf)
+                    .context("Failed to parse identity keypair from protobuf")
+            }
+            // Untagged file: legacy Ed25519 hex.
+            _ => identity::Keypair::ed25519_from_bytes(decoded)
+                .context("Failed to parse legacy identity keypair from bytes"),
+        }
     } else {
-        // Create new keypair
-        let keypair = identity::Keypair::generate_ed25519();
-        let key_bytes = keypair
-            .to_protobuf_encoding()
-            .context("Failed to encode identity to protobuf")?;
-        let hex_encoded = hex::encode(key_bytes);
+        // Create new keypair of the requested type.
+        let (keypair, tag) = match key_type {
+            KeyType::Ed25519 => (identity::Keypair::generate_ed25519(), TAG_ED25519),
+            KeyType::Secp256k1 => (identity::Keypair::generate_secp256k1(), TAG_SECP256K1),
+        };
+        let mut bytes = vec![tag];
+        bytes.extend(
+            keypair
+                .to_protobuf_encoding()
+                .context("Failed to encode identity to protobuf")?,
+        );
+        let hex_encoded = hex::encode(bytes);
         std::fs::write(identity_path, &hex_encoded).context("Failed to write identity file")?;
-        println!("Created new identity at: {}", identity_path.display());
+        println!(
+            "Created new {:?} identity at: {}",
+            key_type,
+            identity_path.display()
+        );
         Ok(keypair)
     }
 }
@@ -82,7 +142,7 @@ async fn main() -> Result<()> {
     tracing::info!("Data directory: {}", cli.data_dir.display());
 
     // Load or create identity
-    let local_key = load_or_create_identity(&identity_path)?;
+    let local_key = load_or_create_identity(&identity_path, cli.key_type)?;
     let local_peer_id = PeerId::from(local_key.public());
     println!("Local Peer ID: {}", local_peer_id);
 
@@ -92,14 +152,15 @@ async fn main() -> Result<()> {
     tracing::debug!("Bootstrap peers: {:?}", cfg.bootstrap_peers);
     tracing::debug!("Topic: {}", cfg.topic);
 
-    // Create Kademlia DHT for peer discovery
-    let kademlia = kad::Behaviour::with_config(
-        local_peer_id,
-        kad::store::MemoryStore::new(local_peer_id),
-        Default::default(),
-    );
+    // The topic every node on this network gossips over.
+    let topic = gossipsub::IdentTopic::new(&cfg.topic);
+
+    // Registry + metrics covering Kademlia/connection/identify events, plus
+    // the per-direction bandwidth counters wrapped around the transport below.
+    let mut registry = Registry::default();
+    let metrics = Metrics::new(&mut registry);
 
-    // Build the Swarm with Kademlia DHT
+    // Build the Swarm with the combined behaviour
     let mut swarm = SwarmBuilder::with_existing_identity(local_key)
         .with_tokio()
         .with_tcp(
@@ -111,9 +172,73 @@ async fn main() -> Result<()> {
         .with_quic()
         .with_dns()
         .unwrap()
-        .with_behaviour(|_| kademlia)?
+        .with_relay_client(
+            (libp2p_tls::Config::new, libp2p_noise::Config::new),
+            libp2p_yamux::Config::default,
+        )
+        .unwrap()
+        .with_bandwidth_metrics(&mut registry)
+        .with_behaviour(|key, relay_client| {
+            // Kademlia DHT for peer discovery
+            let kademlia = kad::Behaviour::with_config(
+                local_peer_id,
+                kad::store::MemoryStore::new(local_peer_id),
+                Default::default(),
+            );
+
+            // GossipSub for topic-based broadcast, messages signed by our identity
+            let gossipsub = gossipsub::Behaviour::new(
+                gossipsub::MessageAuthenticity::Signed(key.clone()),
+                network::gossipsub_config(cfg.network_load)?,
+            )
+            .map_err(|e| anyhow::anyhow!("Failed to build gossipsub behaviour: {}", e))?;
+
+            // Identify exchanges protocol and observed-address info with peers
+            let identify = identify::Behaviour::new(identify::Config::new(
+                "/p2p-experiments/1.0.0".into(),
+                key.public(),
+            ));
+
+            // AutoNAT tells us whether our external address is publicly dialable
+            let autonat = autonat::Behaviour::new(local_peer_id, Default::default());
+
+            // DCUtR upgrades relayed connections to direct ones via hole punching
+            let dcutr = dcutr::Behaviour::new(local_peer_id);
+
+            // Optionally serve as a public relay for NAT'd peers
+            let relay_server = Toggle::from(
+                cfg.enable_relay_server
+                    .then(|| relay::Behaviour::new(local_peer_id, Default::default())),
+            );
+
+            // Enforce connection limits at admission time
+            let peer_manager = PeerManager::new(
+                cfg.max_connections,
+                cfg.max_connections_per_peer,
+                cfg.outbound_connection_slack,
+            );
+
+            Ok(Behaviour {
+                kademlia,
+                gossipsub,
+                identify,
+                autonat,
+                relay_client,
+                dcutr,
+                relay_server,
+                peer_manager,
+            })
+        })?
         .build();
 
+    // Subscribe to the configured topic
+    swarm
+        .behaviour_mut()
+        .gossipsub
+        .subscribe(&topic)
+        .context("Failed to subscribe to topic")?;
+    tracing::info!("Subscribed to topic: {}", cfg.topic);
+
     // Listen on TCP port
     let tcp_listen_addr = format!("/ip4/0.0.0.0/tcp/{}", cli.port);
     Swarm::listen_on(&mut swarm, tcp_listen_addr.parse().unwrap())
@@ -126,64 +251,100 @@ async fn main() -> Result<()> {
         .context("Failed to listen on QUIC port")?;
     tracing::info!("Listening on QUIC port {}", cli.port + 1);
 
-    // Connect to bootstrap peers and add them to DHT
-    for peer in &cfg.bootstrap_peers {
-        match swarm.dial(peer.clone()) {
-            Ok(()) => tracing::info!("Dialing bootstrap peer: {}", peer),
-            Err(e) => tracing::warn!("Failed to dial bootstrap peer {}: {}", peer, e),
-        }
-
-        // Extract peer ID from multiaddr and add to DHT
-        if let Some(peer_id) = peer.iter().find_map(|proto| {
-            if let libp2p::multiaddr::Protocol::P2p(id) = proto {
-                Some(id)
-            } else {
-                None
+    // Optionally expose the registry over a /metrics HTTP endpoint.
+    if let Some(metrics_port) = cli.metrics_port {
+        let registry = Arc::new(Mutex::new(registry));
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(registry, metrics_port).await {
+                tracing::warn!("Metrics endpoint stopped: {}", e);
             }
-        }) {
-            swarm.behaviour_mut().add_address(&peer_id, peer.clone());
-        }
+        });
     }
 
-    // Start DHT bootstrap process
-    swarm.behaviour_mut().bootstrap()?;
+    // Hand the swarm to the event loop and keep a client handle for runtime control.
+    let (client, event_loop) = EventLoop::new(swarm, cfg.relay_peers.clone(), metrics);
+    tokio::spawn(event_loop.run());
 
-    // Event loop
-    println!("P2P node started. Listening for events...");
-    while let Some(event) = swarm.next().await {
-        match event {
-            libp2p::swarm::SwarmEvent::Behaviour(kad::Event::RoutingUpdated { peer, .. }) => {
-                println!("DHT: Routing updated for peer: {}", peer);
-            }
-            libp2p::swarm::SwarmEvent::Behaviour(kad::Event::InboundRequest { request }) => {
-                println!("DHT: Inbound request: {:?}", request);
-            }
-            libp2p::swarm::SwarmEvent::IncomingConnection {
-                local_addr,
-                send_back_addr,
-                connection_id,
-            } => {
-                println!(
-                    "Incoming connection on {}: {} (conn_id: {:?})",
-                    local_addr, send_back_addr, connection_id
-                );
-            }
-            libp2p::swarm::SwarmEvent::ConnectionEstablished {
-                peer_id, endpoint, ..
-            } => {
-                println!("Connection established with {} at {:?}", peer_id, endpoint);
-            }
-            libp2p::swarm::SwarmEvent::ConnectionClosed { peer_id, .. } => {
-                println!("Connection closed with {}", peer_id);
-            }
-            libp2p::swarm::SwarmEvent::Behaviour(event) => {
-                println!("DHT event: {:?}", event);
+    // Connect to bootstrap peers and kick off the DHT bootstrap.
+    client.add_bootstrap_nodes(cfg.bootstrap_peers.clone()).await?;
+    if let Err(e) = client.bootstrap().await {
+        tracing::warn!("Bootstrap failed: {}", e);
+    }
+
+    // Read lines from stdin and publish them to the topic via the client.
+    let mut stdin = BufReader::new(tokio::io::stdin()).lines();
+
+    println!("P2P node started. Type a line and press enter to broadcast.");
+    loop {
+        match stdin.next_line().await {
+            Ok(Some(line)) if !line.is_empty() => {
+                if let Err(e) = client.publish(topic.clone(), line.into_bytes()).await {
+                    tracing::warn!("Failed to publish message: {}", e);
+                }
             }
-            _ => {
-                println!("Swarm event: {:?}", event);
+            Ok(Some(_)) => {}
+            // EOF on stdin: keep running as a pure relay/DHT node.
+            Ok(None) => futures::future::pending::<()>().await,
+            Err(e) => {
+                tracing::warn!("Failed to read from stdin: {}", e);
+                break;
             }
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unique temp path for an identity file, namespaced by test label.
+    fn temp_identity(label: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("p2p-identity-{}-{}", std::process::id(), label))
+    }
+
+    #[test]
+    fn creates_and_reloads_ed25519() {
+        let path = temp_identity("ed25519");
+        let _ = std::fs::remove_file(&path);
+
+        let created = load_or_create_identity(&path, KeyType::Ed25519).unwrap();
+        // File is tagged with the Ed25519 type byte.
+        let decoded = hex::decode(std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(decoded.first(), Some(&TAG_ED25519));
+
+        let reloaded = load_or_create_identity(&path, KeyType::Ed25519).unwrap();
+        assert_eq!(PeerId::from(created.public()), PeerId::from(reloaded.public()));
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn creates_and_reloads_secp256k1() {
+        let path = temp_identity("secp256k1");
+        let _ = std::fs::remove_file(&path);
+
+        let created = load_or_create_identity(&path, KeyType::Secp256k1).unwrap();
+        let decoded = hex::decode(std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(decoded.first(), Some(&TAG_SECP256K1));
+
+        let reloaded = load_or_create_identity(&path, KeyType::Ed25519).unwrap();
+        // Reload dispatches on the stored tag, ignoring the requested type.
+        assert_eq!(PeerId::from(created.public()), PeerId::from(reloaded.public()));
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn loads_untagged_legacy_ed25519_hex() {
+        let path = temp_identity("legacy");
+        // Legacy format: bare hex of a 32-byte Ed25519 secret, no type tag.
+        std::fs::write(&path, hex::encode([7u8; 32])).unwrap();
+
+        let loaded = load_or_create_identity(&path, KeyType::Ed25519).unwrap();
+        assert_eq!(loaded.key_type(), identity::KeyType::Ed25519);
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
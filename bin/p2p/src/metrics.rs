@@ -0,0 +1,58 @@
+//! Prometheus metrics endpoint.
+//!
+//! Serves the shared [`Registry`] over a minimal `/metrics` HTTP endpoint so
+//! the node can be scraped by Prometheus and graphed in Grafana.
+
+use anyhow::{Context, Result};
+use prometheus_client::encoding::text::encode;
+use prometheus_client::registry::Registry;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+
+/// Serve `registry` over `/metrics` on the given port until the process exits.
+pub async fn serve(registry: Arc<Mutex<Registry>>, port: u16) -> Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port))
+        .await
+        .with_context(|| format!("Failed to bind metrics endpoint on port {}", port))?;
+    tracing::info!("Serving Prometheus metrics on 0.0.0.0:{}/metrics", port);
+
+    loop {
+        let (mut socket, _) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                tracing::warn!("Metrics endpoint accept failed: {}", e);
+                continue;
+            }
+        };
+        let registry = registry.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_request(&mut socket, &registry).await {
+                tracing::debug!("Metrics request failed: {}", e);
+            }
+        });
+    }
+}
+
+/// Encode the registry and reply with a single Prometheus text response.
+async fn handle_request<S>(socket: &mut S, registry: &Arc<Mutex<Registry>>) -> Result<()>
+where
+    S: AsyncReadExt + AsyncWriteExt + Unpin,
+{
+    // Drain the request line; we only ever serve `/metrics`.
+    let mut buf = [0u8; 1024];
+    let _ = socket.read(&mut buf).await?;
+
+    let mut body = String::new();
+    encode(&mut body, &*registry.lock().await).context("Failed to encode metrics")?;
+
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/openmetrics-text; version=1.0.0; charset=utf-8\r\nContent-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    socket.write_all(response.as_bytes()).await?;
+    socket.flush().await?;
+    Ok(())
+}
@@ -0,0 +1,447 @@
+//! Swarm event loop and a cloneable client handle.
+//!
+//! The [`EventLoop`] owns the [`Swarm`] and is the only place that touches it
+//! directly. Everything else drives the node through a [`Client`], which sends
+//! [`Command`]s over an mpsc channel and awaits `oneshot` replies. This keeps
+//! the swarm single-threaded while still allowing an interactive CLI or RPC
+//! front-end to dial peers, query the DHT, and publish at runtime.
+
+use anyhow::{Result, anyhow};
+use futures::StreamExt;
+use libp2p::multiaddr::Protocol;
+use libp2p::swarm::behaviour::toggle::Toggle;
+use libp2p::swarm::dial_opts::DialOpts;
+use libp2p::swarm::{NetworkBehaviour, SwarmEvent};
+use crate::peer_manager::PeerManager;
+use libp2p::{Multiaddr, PeerId, Swarm, autonat, dcutr, gossipsub, identify, kad, relay};
+use libp2p_metrics::{Metrics, Recorder};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+/// Combined network behaviour for the node.
+#[derive(NetworkBehaviour)]
+pub struct Behaviour {
+    pub kademlia: kad::Behaviour<kad::store::MemoryStore>,
+    pub gossipsub: gossipsub::Behaviour,
+    pub identify: identify::Behaviour,
+    pub autonat: autonat::Behaviour,
+    pub relay_client: relay::client::Behaviour,
+    pub dcutr: dcutr::Behaviour,
+    pub relay_server: Toggle<relay::Behaviour>,
+    pub peer_manager: PeerManager,
+}
+
+/// Commands issued by a [`Client`] to the [`EventLoop`].
+pub enum Command {
+    Dial {
+        peer_id: PeerId,
+        addr: Multiaddr,
+        sender: oneshot::Sender<Result<()>>,
+    },
+    AddBootstrapNodes {
+        addrs: Vec<Multiaddr>,
+        sender: oneshot::Sender<()>,
+    },
+    GetClosestPeers {
+        key: Vec<u8>,
+        sender: oneshot::Sender<Vec<PeerId>>,
+    },
+    Bootstrap {
+        sender: oneshot::Sender<Result<()>>,
+    },
+    Publish {
+        topic: gossipsub::IdentTopic,
+        data: Vec<u8>,
+        sender: oneshot::Sender<Result<()>>,
+    },
+}
+
+/// Cloneable handle for driving the node from anywhere.
+#[derive(Clone)]
+pub struct Client {
+    sender: mpsc::Sender<Command>,
+}
+
+impl Client {
+    /// Dial a known peer at the given address.
+    pub async fn dial_peer(&self, peer_id: PeerId, addr: Multiaddr) -> Result<()> {
+        let (sender, receiver) = oneshot::channel();
+        self.sender
+            .send(Command::Dial {
+                peer_id,
+                addr,
+                sender,
+            })
+            .await?;
+        receiver.await?
+    }
+
+    /// Add bootstrap multiaddresses to the routing table and dial them.
+    pub async fn add_bootstrap_nodes(&self, addrs: Vec<Multiaddr>) -> Result<()> {
+        let (sender, receiver) = oneshot::channel();
+        self.sender
+            .send(Command::AddBootstrapNodes { addrs, sender })
+            .await?;
+        Ok(receiver.await?)
+    }
+
+    /// Look up the peers closest to `key` in the DHT.
+    pub async fn get_closest_peers(&self, key: impl Into<Vec<u8>>) -> Result<Vec<PeerId>> {
+        let (sender, receiver) = oneshot::channel();
+        self.sender
+            .send(Command::GetClosestPeers {
+                key: key.into(),
+                sender,
+            })
+            .await?;
+        Ok(receiver.await?)
+    }
+
+    /// Kick off the Kademlia bootstrap process.
+    pub async fn bootstrap(&self) -> Result<()> {
+        let (sender, receiver) = oneshot::channel();
+        self.sender.send(Command::Bootstrap { sender }).await?;
+        receiver.await?
+    }
+
+    /// Publish a message to the given topic.
+    pub async fn publish(&self, topic: gossipsub::IdentTopic, data: Vec<u8>) -> Result<()> {
+        let (sender, receiver) = oneshot::channel();
+        self.sender
+            .send(Command::Publish {
+                topic,
+                data,
+                sender,
+            })
+            .await?;
+        receiver.await?
+    }
+}
+
+/// Owns the [`Swarm`] and routes commands and events.
+pub struct EventLoop {
+    swarm: Swarm<Behaviour>,
+    command_receiver: mpsc::Receiver<Command>,
+    relay_peers: Vec<Multiaddr>,
+    /// Relays we've already requested a `/p2p-circuit` reservation from.
+    reserved_relays: HashSet<Multiaddr>,
+    metrics: Metrics,
+    pending_get_closest: HashMap<kad::QueryId, oneshot::Sender<Vec<PeerId>>>,
+    pending_bootstrap: HashMap<kad::QueryId, oneshot::Sender<Result<()>>>,
+}
+
+impl EventLoop {
+    /// Build an event loop owning `swarm` and the matching client handle.
+    pub fn new(
+        swarm: Swarm<Behaviour>,
+        relay_peers: Vec<Multiaddr>,
+        metrics: Metrics,
+    ) -> (Client, Self) {
+        let (command_sender, command_receiver) = mpsc::channel(32);
+        let client = Client {
+            sender: command_sender,
+        };
+        let event_loop = EventLoop {
+            swarm,
+            command_receiver,
+            relay_peers,
+            reserved_relays: HashSet::new(),
+            metrics,
+            pending_get_closest: HashMap::new(),
+            pending_bootstrap: HashMap::new(),
+        };
+        (client, event_loop)
+    }
+
+    /// Run until the command channel closes.
+    pub async fn run(mut self) {
+        loop {
+            tokio::select! {
+                event = self.swarm.next() => {
+                    let Some(event) = event else { break };
+                    self.handle_event(event);
+                }
+                command = self.command_receiver.recv() => {
+                    match command {
+                        Some(command) => self.handle_command(command),
+                        // All clients dropped: shut the loop down.
+                        None => break,
+                    }
+                }
+            }
+        }
+    }
+
+    fn handle_command(&mut self, command: Command) {
+        match command {
+            Command::Dial {
+                peer_id,
+                addr,
+                sender,
+            } => {
+                self.swarm
+                    .behaviour_mut()
+                    .kademlia
+                    .add_address(&peer_id, addr.clone());
+                let opts = DialOpts::peer_id(peer_id).addresses(vec![addr]).build();
+                let _ = sender.send(self.swarm.dial(opts).map_err(Into::into));
+            }
+            Command::AddBootstrapNodes { addrs, sender } => {
+                for addr in addrs {
+                    if let Some(peer_id) = peer_id_from_addr(&addr) {
+                        self.swarm
+                            .behaviour_mut()
+                            .kademlia
+                            .add_address(&peer_id, addr.clone());
+                    }
+                    if let Err(e) = self.swarm.dial(addr.clone()) {
+                        tracing::warn!("Failed to dial bootstrap node {}: {}", addr, e);
+                    }
+                }
+                let _ = sender.send(());
+            }
+            Command::GetClosestPeers { key, sender } => {
+                let query_id = self.swarm.behaviour_mut().kademlia.get_closest_peers(key);
+                self.pending_get_closest.insert(query_id, sender);
+            }
+            Command::Bootstrap { sender } => match self.swarm.behaviour_mut().kademlia.bootstrap() {
+                Ok(query_id) => {
+                    self.pending_bootstrap.insert(query_id, sender);
+                }
+                Err(e) => {
+                    let _ = sender.send(Err(anyhow!("Failed to start bootstrap: {}", e)));
+                }
+            },
+            Command::Publish {
+                topic,
+                data,
+                sender,
+            } => {
+                let result = self
+                    .swarm
+                    .behaviour_mut()
+                    .gossipsub
+                    .publish(topic, data)
+                    .map(|_| ())
+                    .map_err(|e| anyhow!("Failed to publish message: {}", e));
+                let _ = sender.send(result);
+            }
+        }
+    }
+
+    fn handle_event(&mut self, event: SwarmEvent<BehaviourEvent>) {
+        // Record connection and listener metrics for every swarm event.
+        self.metrics.record(&event);
+        match event {
+            SwarmEvent::Behaviour(BehaviourEvent::Kademlia(event)) => {
+                self.metrics.record(&event);
+                match event {
+                    kad::Event::OutboundQueryProgressed {
+                        id, result, step, ..
+                    } => self.handle_query_result(id, result, step.last),
+                    kad::Event::RoutingUpdated { peer, .. } => {
+                        println!("DHT: Routing updated for peer: {}", peer);
+                    }
+                    kad::Event::InboundRequest { request } => {
+                        println!("DHT: Inbound request: {:?}", request);
+                    }
+                    other => tracing::debug!("Kademlia event: {:?}", other),
+                }
+            }
+            SwarmEvent::Behaviour(BehaviourEvent::Gossipsub(gossipsub::Event::Message {
+                propagation_source,
+                message,
+                ..
+            })) => {
+                println!(
+                    "[{}] {}",
+                    propagation_source,
+                    String::from_utf8_lossy(&message.data)
+                );
+            }
+            SwarmEvent::Behaviour(BehaviourEvent::Identify(event)) => {
+                self.metrics.record(&event);
+                // Feed advertised listen addresses into Kademlia to improve routing.
+                if let identify::Event::Received { peer_id, info, .. } = event {
+                    for addr in info.listen_addrs {
+                        self.swarm
+                            .behaviour_mut()
+                            .kademlia
+                            .add_address(&peer_id, addr);
+                    }
+                }
+            }
+            SwarmEvent::Behaviour(BehaviourEvent::Autonat(autonat::Event::StatusChanged {
+                new,
+                ..
+            })) => {
+                println!("AutoNAT: status changed to {:?}", new);
+                // If we're behind NAT, obtain a relay reservation so we stay reachable.
+                if matches!(new, autonat::NatStatus::Private) {
+                    self.request_relay_reservations();
+                }
+            }
+            SwarmEvent::Behaviour(BehaviourEvent::Dcutr(dcutr::Event {
+                remote_peer_id,
+                result,
+            })) => match result {
+                Ok(_) => println!("DCUtR: direct connection established with {}", remote_peer_id),
+                Err(e) => println!("DCUtR: hole punching to {} failed: {}", remote_peer_id, e),
+            },
+            SwarmEvent::IncomingConnection {
+                local_addr,
+                send_back_addr,
+                connection_id,
+            } => {
+                println!(
+                    "Incoming connection on {}: {} (conn_id: {:?})",
+                    local_addr, send_back_addr, connection_id
+                );
+            }
+            SwarmEvent::ConnectionEstablished {
+                peer_id, endpoint, ..
+            } => {
+                println!("Connection established with {} at {:?}", peer_id, endpoint);
+            }
+            SwarmEvent::ConnectionClosed { peer_id, .. } => {
+                println!("Connection closed with {}", peer_id);
+            }
+            SwarmEvent::Behaviour(event) => {
+                println!("Behaviour event: {:?}", event);
+            }
+            _ => {
+                println!("Swarm event: {:?}", event);
+            }
+        }
+    }
+
+    /// Route a finished Kademlia query back to its waiting client, if any.
+    fn handle_query_result(&mut self, id: kad::QueryId, result: kad::QueryResult, last: bool) {
+        match result {
+            kad::QueryResult::GetClosestPeers(result) => {
+                if !last {
+                    return;
+                }
+                if let Some(sender) = self.pending_get_closest.remove(&id) {
+                    let peers = match result {
+                        Ok(ok) => ok.peers.into_iter().map(|p| p.peer_id).collect(),
+                        Err(kad::GetClosestPeersError::Timeout { peers, .. }) => {
+                            peers.into_iter().map(|p| p.peer_id).collect()
+                        }
+                    };
+                    let _ = sender.send(peers);
+                }
+            }
+            kad::QueryResult::Bootstrap(result) => {
+                // Bootstrap progresses through several steps; reply once it's done.
+                if !last {
+                    return;
+                }
+                if let Some(sender) = self.pending_bootstrap.remove(&id) {
+                    let _ = sender
+                        .send(result.map(|_| ()).map_err(|e| anyhow!("Bootstrap failed: {}", e)));
+                }
+            }
+            other => {
+                tracing::debug!("Unhandled query result: {:?}", other);
+            }
+        }
+    }
+
+    /// Dial every configured relay and listen on its `/p2p-circuit` address.
+    fn request_relay_reservations(&mut self) {
+        for relay in self.relay_peers.clone() {
+            // AutoNAT status can flap; only request each reservation once.
+            if !self.reserved_relays.insert(relay.clone()) {
+                continue;
+            }
+            match self.swarm.dial(relay.clone()) {
+                Ok(()) => tracing::info!("Dialing relay peer: {}", relay),
+                Err(e) => tracing::warn!("Failed to dial relay peer {}: {}", relay, e),
+            }
+            let circuit_addr = relay.with(Protocol::P2pCircuit);
+            match self.swarm.listen_on(circuit_addr.clone()) {
+                Ok(_) => tracing::info!("Requesting relay reservation via {}", circuit_addr),
+                Err(e) => {
+                    tracing::warn!("Failed to listen on circuit address {}: {}", circuit_addr, e)
+                }
+            }
+        }
+    }
+}
+
+/// GossipSub mesh parameters for a given network-load level.
+///
+/// Lower levels favour bandwidth (smaller mesh, slower heartbeat); higher
+/// levels favour propagation speed (larger mesh, faster heartbeat). Tuple
+/// layout: `(mesh_n, mesh_n_low, mesh_n_high, gossip_lazy, history_length,
+/// heartbeat_ms)`.
+const LOAD_PROFILES: [(usize, usize, usize, usize, usize, u64); 5] = [
+    (3, 2, 4, 2, 3, 1200),
+    (4, 3, 6, 3, 4, 1000),
+    (6, 4, 12, 6, 5, 700),
+    (8, 6, 14, 8, 6, 600),
+    (10, 8, 16, 10, 8, 500),
+];
+
+/// Build a gossipsub [`Config`](gossipsub::Config) from a validated
+/// network-load level (1-5).
+pub fn gossipsub_config(network_load: u8) -> Result<gossipsub::Config> {
+    // Enforce the 1-5 invariant here rather than relying on the caller, so an
+    // out-of-range level yields an error instead of an underflow/panic.
+    let (mesh_n, mesh_n_low, mesh_n_high, gossip_lazy, history_length, heartbeat_ms) = network_load
+        .checked_sub(1)
+        .and_then(|idx| LOAD_PROFILES.get(usize::from(idx)))
+        .copied()
+        .ok_or_else(|| {
+            anyhow!(
+                "network_load must be between 1 and {}, got {}",
+                LOAD_PROFILES.len(),
+                network_load
+            )
+        })?;
+
+    gossipsub::ConfigBuilder::default()
+        .mesh_n(mesh_n)
+        .mesh_n_low(mesh_n_low)
+        .mesh_n_high(mesh_n_high)
+        // gossipsub rejects a mesh_outbound_min above mesh_n / 2; keep the
+        // default of 2 but lower it for the smallest mesh.
+        .mesh_outbound_min((mesh_n / 2).min(2))
+        .gossip_lazy(gossip_lazy)
+        .history_length(history_length)
+        .heartbeat_interval(Duration::from_millis(heartbeat_ms))
+        .build()
+        .map_err(|e| anyhow!("Failed to build gossipsub config: {}", e))
+}
+
+/// Extract the `/p2p/<peer-id>` component of a multiaddr, if present.
+pub fn peer_id_from_addr(addr: &Multiaddr) -> Option<PeerId> {
+    addr.iter().find_map(|proto| {
+        if let Protocol::P2p(id) = proto {
+            Some(id)
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gossipsub_config_accepts_valid_levels() {
+        for level in 1..=5 {
+            assert!(gossipsub_config(level).is_ok(), "level {} should be valid", level);
+        }
+    }
+
+    #[test]
+    fn gossipsub_config_rejects_out_of_range_levels() {
+        assert!(gossipsub_config(0).is_err());
+        assert!(gossipsub_config(6).is_err());
+        assert!(gossipsub_config(u8::MAX).is_err());
+    }
+}
@@ -0,0 +1,233 @@
+//! Connection budgeting for the node.
+//!
+//! A [`PeerManager`] is a [`NetworkBehaviour`] that refuses over-budget
+//! connections by returning an error from
+//! [`handle_established_inbound_connection`](NetworkBehaviour::handle_established_inbound_connection)
+//! (and the outbound equivalent). This hook fires *after* the transport/noise
+//! upgrade — it's the earliest point at which the peer id is known, which the
+//! per-peer cap needs — so the security handshake has already run by the time
+//! we reject. The truly pre-handshake hook, `handle_pending_inbound_connection`,
+//! can't see the peer id, so it's not usable for peer-keyed limits; for that
+//! reason this layer bounds resource usage rather than providing hard DoS
+//! protection at the socket layer.
+//!
+//! A slice of the total budget is reserved so the node can always reach out to
+//! peers it wants even when inbound demand is high, while the overall
+//! `max_connections` cap still applies to both directions.
+//!
+//! Because refused connections are denied before they establish, only
+//! connections the manager actually admitted ever reach `on_swarm_event`, so
+//! the established/closed counters always stay paired.
+
+use libp2p::PeerId;
+use libp2p::core::Endpoint;
+use libp2p::core::transport::PortUse;
+use libp2p::swarm::{
+    ConnectionDenied, ConnectionId, FromSwarm, NetworkBehaviour, THandler, THandlerInEvent,
+    THandlerOutEvent, ToSwarm, dummy,
+};
+use libp2p::Multiaddr;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::task::{Context, Poll};
+
+/// Error returned when a connection is refused for exceeding the configured
+/// limits.
+#[derive(Debug)]
+struct ConnectionLimitReached;
+
+impl std::fmt::Display for ConnectionLimitReached {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "connection budget exhausted")
+    }
+}
+
+impl std::error::Error for ConnectionLimitReached {}
+
+/// Tracks live connections and enforces connection limits.
+pub struct PeerManager {
+    /// Maximum total simultaneous connections, applied to both directions.
+    max_connections: usize,
+    /// Maximum simultaneous connections per peer, checked when admitting an
+    /// inbound connection; outbound dials are not capped per peer.
+    max_per_peer: usize,
+    /// Connection slots reserved for outbound-only dials.
+    outbound_slack: usize,
+    /// Established connection count per peer.
+    established: HashMap<PeerId, usize>,
+    /// Total established connections.
+    total: usize,
+}
+
+impl PeerManager {
+    /// Build a manager from the configured caps.
+    pub fn new(max_connections: usize, max_per_peer: usize, outbound_slack: usize) -> Self {
+        Self {
+            max_connections,
+            max_per_peer,
+            outbound_slack,
+            established: HashMap::new(),
+            total: 0,
+        }
+    }
+
+    /// Whether an inbound connection from `peer` may be accepted.
+    ///
+    /// Rejected if it would eat into the reserved outbound slack or exceed the
+    /// per-peer cap.
+    fn accept_inbound(&self, peer: &PeerId) -> bool {
+        if self.total + self.outbound_slack >= self.max_connections {
+            return false;
+        }
+        self.established.get(peer).copied().unwrap_or(0) < self.max_per_peer
+    }
+
+    /// Whether an outbound connection may be opened.
+    ///
+    /// Outbound dials only count against the overall `max_connections` cap (the
+    /// reserved slack is there to guarantee them, not to limit them).
+    fn accept_outbound(&self) -> bool {
+        self.total < self.max_connections
+    }
+
+    /// Record a newly established connection.
+    fn on_established(&mut self, peer: PeerId) {
+        self.total += 1;
+        *self.established.entry(peer).or_insert(0) += 1;
+    }
+
+    /// Record a closed connection.
+    fn on_closed(&mut self, peer: &PeerId) {
+        self.total = self.total.saturating_sub(1);
+        if let Some(count) = self.established.get_mut(peer) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                self.established.remove(peer);
+            }
+        }
+    }
+}
+
+impl NetworkBehaviour for PeerManager {
+    type ConnectionHandler = dummy::ConnectionHandler;
+    type ToSwarm = Infallible;
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        _connection_id: ConnectionId,
+        peer: PeerId,
+        _local_addr: &Multiaddr,
+        _remote_addr: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        if !self.accept_inbound(&peer) {
+            tracing::info!("Rejecting inbound connection from {} (over budget)", peer);
+            return Err(ConnectionDenied::new(ConnectionLimitReached));
+        }
+        Ok(dummy::ConnectionHandler)
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        _connection_id: ConnectionId,
+        peer: PeerId,
+        _addr: &Multiaddr,
+        _role_override: Endpoint,
+        _port_use: PortUse,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        if !self.accept_outbound() {
+            tracing::info!("Refusing outbound connection to {} (over budget)", peer);
+            return Err(ConnectionDenied::new(ConnectionLimitReached));
+        }
+        Ok(dummy::ConnectionHandler)
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm) {
+        match event {
+            FromSwarm::ConnectionEstablished(established) => {
+                self.on_established(established.peer_id)
+            }
+            FromSwarm::ConnectionClosed(closed) => self.on_closed(&closed.peer_id),
+            _ => {}
+        }
+    }
+
+    fn on_connection_handler_event(
+        &mut self,
+        _peer: PeerId,
+        _connection_id: ConnectionId,
+        _event: THandlerOutEvent<Self>,
+    ) {
+    }
+
+    fn poll(
+        &mut self,
+        _cx: &mut Context<'_>,
+    ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer() -> PeerId {
+        PeerId::random()
+    }
+
+    #[test]
+    fn accepts_within_budget() {
+        let manager = PeerManager::new(10, 1, 2);
+        assert!(manager.accept_inbound(&peer()));
+    }
+
+    #[test]
+    fn rejects_over_per_peer_cap() {
+        let mut manager = PeerManager::new(10, 1, 2);
+        let p = peer();
+        manager.on_established(p);
+        assert!(!manager.accept_inbound(&p));
+        // A different peer still fits.
+        assert!(manager.accept_inbound(&peer()));
+    }
+
+    #[test]
+    fn reserves_outbound_slack() {
+        // 4 total, 2 reserved for outbound => only 2 inbound slots.
+        let mut manager = PeerManager::new(4, 4, 2);
+        manager.on_established(peer());
+        assert!(manager.accept_inbound(&peer()));
+        manager.on_established(peer());
+        assert!(!manager.accept_inbound(&peer()));
+    }
+
+    #[test]
+    fn established_and_closed_stay_paired() {
+        let mut manager = PeerManager::new(4, 4, 2);
+        let p = peer();
+        manager.on_established(p);
+        manager.on_established(peer());
+        assert_eq!(manager.total, 2);
+        manager.on_closed(&p);
+        assert_eq!(manager.total, 1);
+        assert!(!manager.established.contains_key(&p));
+    }
+
+    #[test]
+    fn outbound_is_capped_by_max_connections() {
+        let mut manager = PeerManager::new(2, 4, 1);
+        assert!(manager.accept_outbound());
+        manager.on_established(peer());
+        assert!(manager.accept_outbound());
+        manager.on_established(peer());
+        // At the total cap, even outbound dials are refused.
+        assert!(!manager.accept_outbound());
+    }
+
+    #[test]
+    fn close_without_establish_does_not_go_negative() {
+        let mut manager = PeerManager::new(4, 4, 2);
+        manager.on_closed(&peer());
+        assert_eq!(manager.total, 0);
+    }
+}